@@ -11,9 +11,19 @@ use reqwest;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::env;
+use std::net::TcpStream;
 use std::path;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use structopt::StructOpt;
+use tiny_http;
+use toml;
+use tungstenite::{connect, Message, WebSocket};
+use tungstenite::stream::MaybeTlsStream;
+
+const EVENTSUB_WS_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
 
 #[derive(Deserialize, Debug)]
 struct TokenResponse {
@@ -25,9 +35,19 @@ struct TokenResponse {
 #[derive(Deserialize, Debug)]
 struct TwitchResponseData<T> {
     data: Vec<T>,
+    #[serde(default)]
+    pagination: Option<TwitchPagination>,
 }
 
 #[derive(Deserialize, Debug)]
+struct TwitchPagination {
+    cursor: Option<String>,
+}
+
+/// Helix caps `user_login`/`login` parameters at this many per request.
+const HELIX_LOGIN_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize, Debug, Clone)]
 struct StreamResponseData {
     id: String,
     user_id: String,
@@ -52,36 +72,150 @@ struct TwitchAuth {
     expires_at: DateTime<Utc>,
 }
 
+#[derive(Deserialize, Debug)]
+struct EventSubMetadata {
+    message_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubMessage {
+    metadata: EventSubMetadata,
+    payload: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubSession {
+    id: String,
+    keepalive_timeout_seconds: Option<u64>,
+    reconnect_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SessionWelcomePayload {
+    session: EventSubSession,
+}
+
+#[derive(Deserialize, Debug)]
+struct SessionReconnectPayload {
+    session: EventSubSession,
+}
+
+#[derive(Deserialize, Debug)]
+struct NotificationPayload {
+    subscription: EventSubSubscriptionInfo,
+    event: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubSubscriptionInfo {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamOnlineEvent {
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamOfflineEvent {
+    broadcaster_user_id: String,
+    broadcaster_user_login: String,
+    broadcaster_user_name: String,
+}
+
+#[derive(Serialize, Debug)]
+struct EventSubSubscriptionRequest<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    version: &'a str,
+    condition: EventSubCondition<'a>,
+    transport: EventSubTransport<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct EventSubCondition<'a> {
+    broadcaster_user_id: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct EventSubTransport<'a> {
+    method: &'a str,
+    session_id: &'a str,
+}
+
 struct TwitchClient {
     http_client: Client,
     client_id: String,
     client_secret: String,
     auth: TwitchAuth,
+    last_validated: std::time::Instant,
 }
 
 impl TwitchClient {
+    /// How often to re-validate the token against Twitch, on top of the
+    /// locally tracked `expires_at`, since that timestamp can drift from
+    /// what Twitch actually thinks (e.g. a manually revoked token).
+    const VALIDATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
     fn new() -> Result<Self> {
         let client_id = env::var("TWITCH_CLIENT_ID").context("TWITCH_CLIENT_ID")?;
         let client_secret = env::var("TWITCH_CLIENT_SECRET").context("TWITCH_CLIENT_SECRET")?;
         let http_client = Client::new();
-        let auth = Self::get_token(&http_client, &client_id, &client_secret)?;
+        let mut auth = Self::get_token(&http_client, &client_id, &client_secret)?;
+        if !Self::validate_token(&http_client, &auth)? {
+            log::info!("cached token failed validation, fetching a fresh one");
+            auth = Self::get_fresh_token(&http_client, &client_id, &client_secret)?;
+            Self::cache_token(&auth)?;
+        }
         Ok(Self {
             http_client,
             client_id,
             client_secret,
             auth,
+            last_validated: std::time::Instant::now(),
         })
     }
 
     fn ensure_token(self: &mut Self) -> Result<()> {
-        if self.auth.expires_at > Utc::now() - Duration::seconds(5) {
+        if self.auth.expires_at <= Utc::now() + Duration::seconds(5) {
             self.auth =
                 Self::get_fresh_token(&self.http_client, &self.client_id, &self.client_secret)?;
             Self::cache_token(&self.auth)?;
+            self.last_validated = std::time::Instant::now();
+            return Ok(());
+        }
+
+        if self.last_validated.elapsed() >= Self::VALIDATE_INTERVAL {
+            self.last_validated = std::time::Instant::now();
+            if !Self::validate_token(&self.http_client, &self.auth)? {
+                log::info!("token failed validation, fetching a fresh one");
+                self.auth = Self::get_fresh_token(
+                    &self.http_client,
+                    &self.client_id,
+                    &self.client_secret,
+                )?;
+                Self::cache_token(&self.auth)?;
+            }
         }
+
         Ok(())
     }
 
+    /// Ask Twitch whether `auth.access_token` is still considered valid.
+    /// The locally cached `expires_at` can't be trusted alone: a token
+    /// can be revoked or otherwise invalidated server-side before then.
+    fn validate_token(http_client: &Client, auth: &TwitchAuth) -> Result<bool> {
+        let resp = http_client
+            .get("https://id.twitch.tv/oauth2/validate")
+            .header("Authorization", format!("OAuth {}", auth.access_token))
+            .send()?;
+        Ok(resp.status().is_success())
+    }
+
     /// Get an existing token from the cached file, or fetch a
     /// new one from the twitch API if expired or not present.
     /// When getting a new file, will also cache it locally.
@@ -172,58 +306,799 @@ impl TwitchClient {
         self: &mut Self,
         user_logins: &[T],
     ) -> Result<TwitchResponseData<StreamResponseData>> {
-        self.ensure_token()?;
-        let query: Vec<_> = user_logins.iter().map(|u| ("user_login", *u)).collect();
-        let req = self
-            .http_client
-            .get("https://api.twitch.tv/helix/streams")
-            .header("Client-Id", &self.auth.client_id)
-            .bearer_auth(&self.auth.access_token)
-            .query(&query);
-
-        Ok(req.send()?.json()?)
+        let mut data = Vec::new();
+        for chunk in user_logins.chunks(HELIX_LOGIN_BATCH_SIZE) {
+            let query: Vec<_> = chunk.iter().map(|u| ("user_login", *u)).collect();
+            data.extend(self.fetch_helix_pages("https://api.twitch.tv/helix/streams", &query)?);
+        }
+        Ok(TwitchResponseData {
+            data,
+            pagination: None,
+        })
     }
 
     fn get_users<T: Serialize + Copy>(
         self: &mut Self,
         user_logins: &[T]
     ) -> Result<TwitchResponseData<UserResponseData>> {
+        let mut data = Vec::new();
+        for chunk in user_logins.chunks(HELIX_LOGIN_BATCH_SIZE) {
+            let query: Vec<_> = chunk.iter().map(|u| ("login", *u)).collect();
+            data.extend(self.fetch_helix_pages("https://api.twitch.tv/helix/users", &query)?);
+        }
+        Ok(TwitchResponseData {
+            data,
+            pagination: None,
+        })
+    }
+
+    /// Fetch every page for a single batch of `query` params (already
+    /// within Helix's 100-item cap), following `pagination.cursor` until
+    /// exhausted.
+    fn fetch_helix_pages<T: Serialize + Copy, R: serde::de::DeserializeOwned>(
+        self: &mut Self,
+        url: &str,
+        query: &[(&str, T)],
+    ) -> Result<Vec<R>> {
+        let mut data = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page: TwitchResponseData<R> = self.fetch_helix_page(url, query, cursor.as_deref())?;
+            let next_cursor = page
+                .pagination
+                .and_then(|p| p.cursor)
+                .filter(|c| !c.is_empty());
+            data.extend(page.data);
+            match next_cursor {
+                Some(c) => cursor = Some(c),
+                None => break,
+            }
+        }
+        Ok(data)
+    }
+
+    /// Issue a single Helix GET, retrying once on a `401` after
+    /// refreshing the token.
+    fn fetch_helix_page<T: Serialize + Copy, R: serde::de::DeserializeOwned>(
+        self: &mut Self,
+        url: &str,
+        query: &[(&str, T)],
+        after: Option<&str>,
+    ) -> Result<TwitchResponseData<R>> {
+        self.ensure_token()?;
+        let build_req = |client: &Client, auth: &TwitchAuth| {
+            let req = client
+                .get(url)
+                .header("Client-Id", &auth.client_id)
+                .bearer_auth(&auth.access_token)
+                .query(query);
+            match after {
+                Some(after) => req.query(&[("after", after)]),
+                None => req,
+            }
+        };
+
+        let resp = build_req(&self.http_client, &self.auth).send()?;
+
+        if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+            log::warn!("got 401 fetching {}, refreshing token and retrying once", url);
+            self.refresh_token()?;
+            let resp = build_req(&self.http_client, &self.auth).send()?;
+            return Ok(resp.error_for_status()?.json()?);
+        }
+
+        Ok(resp.error_for_status()?.json()?)
+    }
+
+    fn refresh_token(self: &mut Self) -> Result<()> {
+        self.auth = Self::get_fresh_token(&self.http_client, &self.client_id, &self.client_secret)?;
+        Self::cache_token(&self.auth)?;
+        self.last_validated = std::time::Instant::now();
+        Ok(())
+    }
+
+    fn subscribe_eventsub(
+        self: &mut Self,
+        kind: &str,
+        broadcaster_user_id: &str,
+        session_id: &str,
+    ) -> Result<()> {
         self.ensure_token()?;
-        let query: Vec<_> = user_logins.iter().map(|u| ("login", *u)).collect();
+        let body = EventSubSubscriptionRequest {
+            kind,
+            version: "1",
+            condition: EventSubCondition {
+                broadcaster_user_id,
+            },
+            transport: EventSubTransport {
+                method: "websocket",
+                session_id,
+            },
+        };
         let req = self
             .http_client
-            .get("https://api.twitch.tv/helix/users")
+            .post("https://api.twitch.tv/helix/eventsub/subscriptions")
             .header("Client-Id", &self.auth.client_id)
             .bearer_auth(&self.auth.access_token)
-            .query(&query);
+            .json(&body);
+
+        let resp = req.send()?;
+        if resp.status() == reqwest::StatusCode::CONFLICT {
+            log::debug!(
+                "eventsub subscription {} for {} already exists, ignoring",
+                kind,
+                broadcaster_user_id
+            );
+            return Ok(());
+        }
+        resp.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Connect to the EventSub websocket, wait for the `session_welcome`
+/// message, subscribe to `stream.online`/`stream.offline` for every
+/// watched user, then dispatch notifications as events come in.
+/// Reconnects (following `session_reconnect` or on a keepalive
+/// timeout/dropped connection) for as long as the process runs.
+///
+/// Twitch carries existing subscriptions over to the new session after
+/// a `session_reconnect`, so we only (re)subscribe on the very first
+/// connection and after a session was lost outright (keepalive timeout,
+/// dropped socket, ...) and has to be re-established from scratch.
+fn run_eventsub(
+    twitch_client: &mut TwitchClient,
+    user_map: &BTreeMap<String, &UserResponseData>,
+    stream_settings: &BTreeMap<String, StreamConfig>,
+    cooldowns: &CooldownConfig,
+    dashboard: Option<&DashboardHandle>,
+) -> Result<()> {
+    let mut connect_url = EVENTSUB_WS_URL.to_string();
+    let mut last_fired: BTreeMap<(String, EventKind), std::time::Instant> = BTreeMap::new();
+    let mut needs_subscribe = true;
+
+    loop {
+        let (mut socket, _resp) =
+            connect(&connect_url).context("cannot connect to the eventsub websocket")?;
+
+        let welcome: EventSubMessage = read_eventsub_message(&mut socket)?;
+        if welcome.metadata.message_type != "session_welcome" {
+            return Err(anyhow!(
+                "expected session_welcome, got {}",
+                welcome.metadata.message_type
+            ));
+        }
+        let welcome_payload: SessionWelcomePayload = serde_json::from_value(welcome.payload)?;
+        let session_id = welcome_payload.session.id;
+        let keepalive_timeout = std::time::Duration::from_secs(
+            welcome_payload.session.keepalive_timeout_seconds.unwrap_or(10) + 5,
+        );
+
+        if needs_subscribe {
+            for user_id in user_map.keys() {
+                twitch_client.subscribe_eventsub("stream.online", user_id, &session_id)?;
+                twitch_client.subscribe_eventsub("stream.offline", user_id, &session_id)?;
+            }
+            log::info!("subscribed to eventsub for {} users", user_map.len());
+        } else {
+            log::debug!("subscriptions carried over from the previous session, not resubscribing");
+        }
 
-        Ok(req.send()?.json()?)
+        connect_url = match drive_eventsub_session(
+            socket,
+            user_map,
+            stream_settings,
+            cooldowns,
+            &mut last_fired,
+            dashboard,
+            keepalive_timeout,
+        ) {
+            Ok(reconnect_url) => {
+                needs_subscribe = false;
+                reconnect_url
+            }
+            Err(err) => {
+                log::warn!("eventsub session dropped: {}, reconnecting", err);
+                needs_subscribe = true;
+                EVENTSUB_WS_URL.to_string()
+            }
+        };
     }
 }
 
+/// Read frames off an already-subscribed session until it needs to be
+/// replaced: returns `Ok` with the `reconnect_url` Twitch asked us to
+/// move to, or an error once the connection was lost (the caller then
+/// reconnects to the default url).
+///
+/// `socket.read()` has no portable way to time out across the TLS
+/// backends `MaybeTlsStream` can wrap (setting a read timeout on the
+/// raw TCP stream only works for the plain `ws://` variant, never hit
+/// by `EVENTSUB_WS_URL`'s `wss://`). So reads happen on a background
+/// thread that forwards parsed messages over a channel, and this loop
+/// uses `recv_timeout` against `keepalive_timeout_seconds` to notice a
+/// silently dropped connection and bail out for a reconnect. If we bail
+/// out this way, the reader thread is left blocked in `socket.read()`
+/// until the connection actually errors out or the process exits.
+fn drive_eventsub_session(
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    user_map: &BTreeMap<String, &UserResponseData>,
+    stream_settings: &BTreeMap<String, StreamConfig>,
+    cooldowns: &CooldownConfig,
+    last_fired: &mut BTreeMap<(String, EventKind), std::time::Instant>,
+    dashboard: Option<&DashboardHandle>,
+    keepalive_timeout: std::time::Duration,
+) -> Result<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut socket = socket;
+        loop {
+            let msg = read_eventsub_message(&mut socket);
+            let is_err = msg.is_err();
+            if tx.send(msg).is_err() || is_err {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let msg = match rx.recv_timeout(keepalive_timeout) {
+            Ok(msg) => msg?,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                return Err(anyhow!(
+                    "no eventsub message received within {:?}",
+                    keepalive_timeout
+                ));
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(anyhow!("eventsub reader thread exited"));
+            }
+        };
+
+        match msg.metadata.message_type.as_str() {
+            "session_keepalive" => {
+                log::debug!("eventsub keepalive");
+            }
+            "session_reconnect" => {
+                let payload: SessionReconnectPayload = serde_json::from_value(msg.payload)?;
+                let reconnect_url = payload
+                    .session
+                    .reconnect_url
+                    .ok_or(anyhow!("session_reconnect without a reconnect_url"))?;
+                return Ok(reconnect_url);
+            }
+            "notification" => {
+                let payload: NotificationPayload = serde_json::from_value(msg.payload)?;
+                handle_eventsub_notification(
+                    &payload,
+                    user_map,
+                    stream_settings,
+                    cooldowns,
+                    last_fired,
+                    dashboard,
+                )?;
+            }
+            other => {
+                log::debug!("ignoring eventsub message of type {}", other);
+            }
+        }
+    }
+}
+
+fn handle_eventsub_notification(
+    payload: &NotificationPayload,
+    user_map: &BTreeMap<String, &UserResponseData>,
+    stream_settings: &BTreeMap<String, StreamConfig>,
+    cooldowns: &CooldownConfig,
+    last_fired: &mut BTreeMap<(String, EventKind), std::time::Instant>,
+    dashboard: Option<&DashboardHandle>,
+) -> Result<()> {
+    match payload.subscription.kind.as_str() {
+        "stream.online" => {
+            let event: StreamOnlineEvent = serde_json::from_value(payload.event.clone())?;
+            let display_name = user_map
+                .get(&event.broadcaster_user_id)
+                .map(|u| u.display_name.as_str())
+                .unwrap_or(&event.broadcaster_user_name);
+            let settings = stream_settings
+                .get(&event.broadcaster_user_login)
+                .cloned()
+                .unwrap_or_default();
+            if settings.notify_live
+                && should_notify(
+                    last_fired,
+                    cooldowns,
+                    &event.broadcaster_user_id,
+                    EventKind::Live,
+                )
+            {
+                Notification::new()
+                    .summary(display_name)
+                    .body(&format!("Went live at {}", event.started_at))
+                    .show()?;
+            }
+            if let Some(dashboard) = dashboard {
+                // EventSub's stream.online event carries no viewer count or
+                // game, unlike the polling loop's Helix response, so the
+                // dashboard only learns that the stream is live here.
+                dashboard.update(
+                    &event.broadcaster_user_id,
+                    StreamStatus {
+                        user: display_name.to_string(),
+                        live: true,
+                        viewer_count: 0,
+                        game_name: String::new(),
+                    },
+                );
+            }
+        }
+        "stream.offline" => {
+            let event: StreamOfflineEvent = serde_json::from_value(payload.event.clone())?;
+            let display_name = user_map
+                .get(&event.broadcaster_user_id)
+                .map(|u| u.display_name.as_str())
+                .unwrap_or(&event.broadcaster_user_name);
+            let settings = stream_settings
+                .get(&event.broadcaster_user_login)
+                .cloned()
+                .unwrap_or_default();
+            if settings.notify_live
+                && should_notify(
+                    last_fired,
+                    cooldowns,
+                    &event.broadcaster_user_id,
+                    EventKind::Offline,
+                )
+            {
+                Notification::new()
+                    .summary(display_name)
+                    .body("Stream ended")
+                    .show()?;
+            }
+            if let Some(dashboard) = dashboard {
+                dashboard.update(
+                    &event.broadcaster_user_id,
+                    StreamStatus {
+                        user: display_name.to_string(),
+                        live: false,
+                        viewer_count: 0,
+                        game_name: String::new(),
+                    },
+                );
+            }
+        }
+        other => {
+            log::debug!("ignoring notification for subscription type {}", other);
+        }
+    }
+    Ok(())
+}
+
+fn read_eventsub_message(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<EventSubMessage> {
+    loop {
+        match socket.read()? {
+            Message::Text(txt) => return Ok(serde_json::from_str(&txt)?),
+            Message::Binary(_) | Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {
+                continue;
+            }
+            Message::Close(_) => return Err(anyhow!("eventsub websocket closed")),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct StreamStatus {
+    user: String,
+    live: bool,
+    viewer_count: u32,
+    game_name: String,
+}
+
+/// Handle shared between the main loop and the dashboard HTTP server:
+/// the authoritative per-user status, and the list of currently
+/// connected SSE subscribers to push updates to.
+#[derive(Clone)]
+struct DashboardHandle {
+    state: Arc<Mutex<BTreeMap<String, StreamStatus>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<String>>>>,
+}
+
+impl DashboardHandle {
+    fn new() -> Self {
+        DashboardHandle {
+            state: Arc::new(Mutex::new(BTreeMap::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Update the status for `user_id` and, if it actually changed,
+    /// broadcast it as an SSE `data:` frame to every connected browser.
+    fn update(self: &Self, user_id: &str, status: StreamStatus) {
+        let mut state = self.state.lock().unwrap();
+        if state.get(user_id) == Some(&status) {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(&status) {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|tx| tx.send(json.clone()).is_ok());
+        }
+        state.insert(user_id.to_string(), status);
+    }
+
+    fn subscribe(self: &Self) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        {
+            let state = self.state.lock().unwrap();
+            for status in state.values() {
+                if let Ok(json) = serde_json::to_string(status) {
+                    let _ = tx.send(json);
+                }
+            }
+        }
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+}
+
+const DASHBOARD_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>twitch-watcher dashboard</title>
+  <style>
+    body { font-family: sans-serif; margin: 2rem; }
+    table { border-collapse: collapse; }
+    td, th { padding: 0.3rem 0.8rem; border-bottom: 1px solid #ccc; text-align: left; }
+    .live { color: #1f9d55; font-weight: bold; }
+    .offline { color: #888; }
+  </style>
+</head>
+<body>
+  <h1>Watched streams</h1>
+  <table id="streams">
+    <thead><tr><th>User</th><th>Status</th><th>Viewers</th><th>Game</th></tr></thead>
+    <tbody></tbody>
+  </table>
+  <script>
+    const rows = {};
+    const tbody = document.querySelector("#streams tbody");
+
+    function render(status) {
+      let row = rows[status.user];
+      if (!row) {
+        row = document.createElement("tr");
+        row.innerHTML = "<td></td><td></td><td></td><td></td>";
+        tbody.appendChild(row);
+        rows[status.user] = row;
+      }
+      const cells = row.children;
+      cells[0].textContent = status.user;
+      cells[1].textContent = status.live ? "live" : "offline";
+      cells[1].className = status.live ? "live" : "offline";
+      cells[2].textContent = status.viewer_count;
+      cells[3].textContent = status.game_name;
+    }
+
+    const events = new EventSource("/events");
+    events.onmessage = (e) => render(JSON.parse(e.data));
+  </script>
+</body>
+</html>
+"##;
+
+/// How often an idle SSE connection gets a `:heartbeat` comment frame.
+/// Without it, `read()` would block in `rx.recv()` forever whenever the
+/// watched streams' state stays unchanged, so `tiny_http` never attempts
+/// a write and never notices a browser tab closing the connection —
+/// leaking one thread and channel per disconnected client for the life
+/// of the process. Forcing a periodic write surfaces that broken pipe.
+const SSE_HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Adapts a `Receiver<String>` of already-serialized JSON payloads into
+/// a `Read` of SSE `data:` frames, for `tiny_http`'s streaming response.
+struct SseReader {
+    rx: mpsc::Receiver<String>,
+    buf: VecDeque<u8>,
+}
+
+impl SseReader {
+    fn new(rx: mpsc::Receiver<String>) -> Self {
+        SseReader {
+            rx,
+            buf: VecDeque::new(),
+        }
+    }
+}
+
+impl std::io::Read for SseReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.buf.is_empty() {
+            match self.rx.recv_timeout(SSE_HEARTBEAT_INTERVAL) {
+                Ok(payload) => self.buf.extend(format!("data: {}\n\n", payload).into_bytes()),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    self.buf.extend(b": heartbeat\n\n".iter().copied())
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len());
+        for byte in out.iter_mut().take(n) {
+            *byte = self.buf.pop_front().unwrap();
+        }
+        Ok(n)
+    }
+}
+
+/// Run the dashboard HTTP server: `/events` upgrades to an SSE stream of
+/// `StreamStatus` updates, anything else serves the static page that
+/// subscribes to it. Spawns one thread per request so a long-lived SSE
+/// connection doesn't block new visitors.
+fn run_dashboard_server(addr: &str, dashboard: DashboardHandle) -> Result<()> {
+    let server =
+        tiny_http::Server::http(addr).map_err(|e| anyhow!("cannot bind dashboard to {}: {}", addr, e))?;
+    log::info!("dashboard listening on http://{}", addr);
+
+    for request in server.incoming_requests() {
+        let dashboard = dashboard.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = handle_dashboard_request(request, &dashboard) {
+                log::warn!("dashboard request failed: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_dashboard_request(request: tiny_http::Request, dashboard: &DashboardHandle) -> Result<()> {
+    if request.url() == "/events" {
+        let rx = dashboard.subscribe();
+        let response = tiny_http::Response::empty(200)
+            .with_data(SseReader::new(rx), None)
+            .with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/event-stream"[..])
+                    .map_err(|_| anyhow!("invalid header"))?,
+            );
+        request.respond(response)?;
+    } else {
+        let response = tiny_http::Response::from_string(DASHBOARD_HTML).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+                .map_err(|_| anyhow!("invalid header"))?,
+        );
+        request.respond(response)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Config {
+    #[serde(default)]
+    streams: Vec<StreamConfig>,
+    #[serde(default)]
+    cooldowns: CooldownConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct StreamConfig {
+    login: String,
+    #[serde(default = "default_true")]
+    notify_viewer_count: bool,
+    #[serde(default = "default_true")]
+    notify_live: bool,
+    #[serde(default = "default_true")]
+    notify_game_change: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            login: String::new(),
+            notify_viewer_count: true,
+            notify_live: true,
+            notify_game_change: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct CooldownConfig {
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    global: Option<std::time::Duration>,
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    viewer_cooldown: Option<std::time::Duration>,
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    live_cooldown: Option<std::time::Duration>,
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    game_change_cooldown: Option<std::time::Duration>,
+}
+
+impl CooldownConfig {
+    fn cooldown_for(&self, kind: EventKind) -> Option<std::time::Duration> {
+        let specific = match kind {
+            EventKind::Live | EventKind::Offline => self.live_cooldown,
+            EventKind::GameChange => self.game_change_cooldown,
+            EventKind::ViewerCount => self.viewer_cooldown,
+        };
+        specific.or(self.global)
+    }
+}
+
+fn deserialize_opt_duration<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw: Option<String> = Option::deserialize(deserializer)?;
+    match raw {
+        Some(s) => parse_duration(&s)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Parse a duration like `"60s"`, `"5m"` or `"2h"` (a bare number is
+/// treated as seconds).
+fn parse_duration(s: &str) -> Result<std::time::Duration> {
+    let s = s.trim();
+    let split_at = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num_part, unit) = s.split_at(split_at);
+    let n: u64 = num_part
+        .parse()
+        .with_context(|| format!("invalid duration: {:?}", s))?;
+    let secs = match unit {
+        "" | "s" => n,
+        "m" => n * 60,
+        "h" => n * 3600,
+        other => return Err(anyhow!("unknown duration unit {:?} in {:?}", other, s)),
+    };
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+/// The kind of notification a cooldown can be configured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    Live,
+    Offline,
+    GameChange,
+    ViewerCount,
+}
+
+fn load_config() -> Result<Config> {
+    let config_path = get_config_path()?;
+    match std::fs::read_to_string(&config_path) {
+        Ok(content) => toml::from_str(&content)
+            .with_context(|| format!("cannot parse config from {:?}", &config_path)),
+        Err(_) => {
+            log::info!("no config file at {:?}, using defaults", config_path);
+            Ok(Config::default())
+        }
+    }
+}
+
+fn get_config_path() -> Result<path::PathBuf> {
+    let project_dirs =
+        directories::ProjectDirs::from("geekingfrog", "geekingfrog", "twitch-notif-daemon")
+            .ok_or(anyhow!("cannot construct project directories"))?;
+
+    Ok(project_dirs.config_dir().join("config.toml"))
+}
+
+/// Returns whether a notification of `kind` for `user_id` should fire,
+/// given the configured cooldowns. Records the firing time so the next
+/// call can suppress a too-recent repeat.
+fn should_notify(
+    last_fired: &mut BTreeMap<(String, EventKind), std::time::Instant>,
+    cooldowns: &CooldownConfig,
+    user_id: &str,
+    kind: EventKind,
+) -> bool {
+    let now = std::time::Instant::now();
+    let key = (user_id.to_string(), kind);
+    if let Some(cooldown) = cooldowns.cooldown_for(kind) {
+        if let Some(last) = last_fired.get(&key) {
+            if now.duration_since(*last) < cooldown {
+                return false;
+            }
+        }
+    }
+    last_fired.insert(key, now);
+    true
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "twitch streams watcher")]
 struct Opt {
     #[structopt(help = "space separated list of streams to watch")]
     target_streams: Vec<String>,
+
+    #[structopt(
+        long,
+        help = "drive notifications from the EventSub websocket instead of polling"
+    )]
+    eventsub: bool,
+
+    #[structopt(
+        long,
+        help = "serve a live dashboard over HTTP at this address, e.g. 127.0.0.1:8080"
+    )]
+    serve: Option<String>,
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let opt = Opt::from_args();
+    let config = load_config()?;
 
     let mut twitch_client = TwitchClient::new()?;
     // let target_stream = "gikiam";
     let appname = "stream watcher";
     let d = Duration::seconds(10).to_std()?;
 
-    let target_streams = opt.target_streams.iter().map(|x| &**x).collect::<Vec<_>>();
+    // Twitch logins are case-insensitive and `get_users` always returns
+    // them lowercased, so normalize here too or a config/CLI login with
+    // different casing would silently fail to match.
+    let mut target_logins: Vec<String> = opt
+        .target_streams
+        .iter()
+        .map(|s| s.to_lowercase())
+        .collect();
+    for stream in &config.streams {
+        let login = stream.login.to_lowercase();
+        if !target_logins.contains(&login) {
+            target_logins.push(login);
+        }
+    }
+    if target_logins.is_empty() {
+        return Err(anyhow!(
+            "no stream to watch: pass some on the command line or add them to the config file"
+        ));
+    }
+    let stream_settings: BTreeMap<_, _> = config
+        .streams
+        .iter()
+        .map(|s| (s.login.to_lowercase(), s.clone()))
+        .collect();
+
+    let target_streams = target_logins.iter().map(|x| &**x).collect::<Vec<_>>();
     let users = twitch_client.get_users(&target_streams[..])?.data;
     let user_map = users.iter().map(|u| (u.id.clone(), u)).collect::<BTreeMap<_,_>>();
     log::debug!("user ids: {:#?}", users);
 
+    let dashboard = opt.serve.as_ref().map(|addr| {
+        let dashboard = DashboardHandle::new();
+        let server_dashboard = dashboard.clone();
+        let addr = addr.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = run_dashboard_server(&addr, server_dashboard) {
+                log::error!("dashboard server stopped: {}", err);
+            }
+        });
+        dashboard
+    });
+
+    if opt.eventsub {
+        return run_eventsub(
+            &mut twitch_client,
+            &user_map,
+            &stream_settings,
+            &config.cooldowns,
+            dashboard.as_ref(),
+        );
+    }
+
     let stream_resp = twitch_client.get_streams_data(&target_streams[..])?;
 
+    let mut live_streams: BTreeMap<_, _> = stream_resp
+        .data
+        .iter()
+        .map(|d| (d.user_id.clone(), d.clone()))
+        .collect();
+
     let mut viewer_counts: BTreeMap<_, _> = stream_resp
         .data
         .iter()
@@ -257,25 +1132,104 @@ fn main() -> Result<()> {
     // alternate endpoint to get the list of registered users on the chat
     // https://tmi.twitch.tv/group/user/USERNAME/chatters
 
+    let mut last_fired: BTreeMap<(String, EventKind), std::time::Instant> = BTreeMap::new();
+
     loop {
         let data = twitch_client.get_streams_data(&target_streams[..])?.data;
+        let current_live_streams: BTreeMap<_, _> = data
+            .iter()
+            .map(|d| (d.user_id.clone(), d.clone()))
+            .collect();
         let current_viewer_counts: BTreeMap<_, _> = data
             .iter()
             .map(|d| (d.user_id.clone(), d.viewer_count))
             .collect();
 
         for (user_id, user) in user_map.iter() {
-            let prev_count = viewer_counts.get(user_id).unwrap_or(&0);
-            let current_count = current_viewer_counts.get(user_id).unwrap_or(&0);
-            log::debug!("current viewer count for {}: {}", user.display_name, current_count);
-            if prev_count != current_count {
-                Notification::new()
-                    .summary(&user.display_name)
-                    .body(&format!("Updated viewer count: {}", current_count))
-                    .show()?;
+            let settings = stream_settings
+                .get(&user.login)
+                .cloned()
+                .unwrap_or_default();
+            let prev_stream = live_streams.get(user_id);
+            let current_stream = current_live_streams.get(user_id);
+
+            match (prev_stream, current_stream) {
+                (None, Some(stream)) => {
+                    if settings.notify_live
+                        && should_notify(&mut last_fired, &config.cooldowns, user_id, EventKind::Live)
+                    {
+                        Notification::new()
+                            .summary(&user.display_name)
+                            .body(&format!(
+                                "Went live playing {} ({} viewer) - started at {}",
+                                stream.game_name, stream.viewer_count, stream.started_at
+                            ))
+                            .show()?;
+                    }
+                }
+                (Some(_), None) => {
+                    if settings.notify_live
+                        && should_notify(&mut last_fired, &config.cooldowns, user_id, EventKind::Offline)
+                    {
+                        Notification::new()
+                            .summary(&user.display_name)
+                            .body("Stream ended")
+                            .show()?;
+                    }
+                }
+                (Some(prev), Some(current)) => {
+                    if prev.game_id != current.game_id
+                        && settings.notify_game_change
+                        && should_notify(
+                            &mut last_fired,
+                            &config.cooldowns,
+                            user_id,
+                            EventKind::GameChange,
+                        )
+                    {
+                        Notification::new()
+                            .summary(&user.display_name)
+                            .body(&format!("switched to {}", current.game_name))
+                            .show()?;
+                    }
+
+                    let prev_count = viewer_counts.get(user_id).unwrap_or(&0);
+                    let current_count = current_viewer_counts.get(user_id).unwrap_or(&0);
+                    log::debug!(
+                        "current viewer count for {}: {}",
+                        user.display_name,
+                        current_count
+                    );
+                    if prev_count != current_count
+                        && settings.notify_viewer_count
+                        && should_notify(
+                            &mut last_fired,
+                            &config.cooldowns,
+                            user_id,
+                            EventKind::ViewerCount,
+                        )
+                    {
+                        Notification::new()
+                            .summary(&user.display_name)
+                            .body(&format!("Updated viewer count: {}", current_count))
+                            .show()?;
+                    }
+                }
+                (None, None) => (),
+            }
+
+            if let Some(dashboard) = &dashboard {
+                let status = StreamStatus {
+                    user: user.display_name.clone(),
+                    live: current_stream.is_some(),
+                    viewer_count: current_stream.map(|s| s.viewer_count).unwrap_or(0),
+                    game_name: current_stream.map(|s| s.game_name.clone()).unwrap_or_default(),
+                };
+                dashboard.update(user_id, status);
             }
         }
 
+        live_streams = current_live_streams;
         viewer_counts = current_viewer_counts;
         std::thread::sleep(d);
     }